@@ -0,0 +1,675 @@
+//! Backends for the forges we can talk to: GitHub (including GitHub
+//! Enterprise), GitLab, and sourcehut. Each exposes the same two
+//! operations -- listing a directory and resolving a single file -- behind
+//! whichever API shape and auth header convention that forge actually uses,
+//! so the rest of the crate never has to branch on host again.
+
+use std::error::Error;
+use std::fmt;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use reqwest::blocking::{Client, Response};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Which forge a repo link points at. GitHub Enterprise is represented by
+/// `GitHub` too -- it speaks the same Contents API, just against a
+/// different `api_base` (see [`default_api_base`]).
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum HostKind {
+    GitHub,
+    GitLab,
+    SourceHut,
+}
+
+impl Default for HostKind {
+    /// Metadata files written before multi-host support existed never
+    /// recorded a host; they were always github.com.
+    fn default() -> Self {
+        HostKind::GitHub
+    }
+}
+
+/// A file or directory entry returned while listing a remote directory (or
+/// looking up a single `/blob/` file), normalized across forges. `sha` is
+/// the host's git blob/tree object id; `download_url` is empty for
+/// directories.
+pub struct RemoteEntry {
+    pub name: String,
+    /// Path relative to the repo root.
+    pub path: String,
+    pub is_dir: bool,
+    pub sha: String,
+    pub download_url: String,
+}
+
+/// Marks a listing/fetch that failed because the remote path genuinely
+/// doesn't exist (HTTP 404), as opposed to any other failure. Callers use
+/// this to tell "skip, it's gone" apart from "abort, something's wrong".
+#[derive(Debug)]
+pub struct NotFound;
+
+impl fmt::Display for NotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "remote path not found")
+    }
+}
+
+impl Error for NotFound {}
+
+fn rate_limit_error(host: &HostKind) -> Box<dyn Error> {
+    let env_var = match host {
+        HostKind::GitHub => "GITHUB_TOKEN",
+        HostKind::GitLab => "GITLAB_TOKEN",
+        HostKind::SourceHut => "SRHT_TOKEN",
+    };
+    format!(
+        "HTTP 403 Forbidden. Are you hitting a rate limit? Try setting the {} environment variable, or raise --max-retries.",
+        env_var
+    )
+    .into()
+}
+
+/// True for a 403/429 that looks like a rate limit rather than a plain
+/// permission error: GitHub/GitLab both send `Retry-After` for secondary
+/// (abuse-detection) limits, and GitHub sends `X-RateLimit-Remaining: 0`
+/// once the primary quota is exhausted. A bare 403 with neither header is
+/// a real "you can't access this", not worth retrying.
+fn is_rate_limited(resp: &Response) -> bool {
+    let status = resp.status().as_u16();
+    if status == 429 {
+        return true;
+    }
+    if status != 403 {
+        return false;
+    }
+    resp.headers().contains_key("Retry-After")
+        || resp.headers().get("X-RateLimit-Remaining").and_then(|v| v.to_str().ok()) == Some("0")
+}
+
+/// How long to wait before the next retry: honor `Retry-After` or
+/// `X-RateLimit-Reset` if the server sent one, otherwise fall back to
+/// exponential backoff.
+fn retry_delay(resp: &Response, attempt: u32) -> Duration {
+    if let Some(secs) = resp
+        .headers()
+        .get("Retry-After")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+    {
+        return Duration::from_secs(secs.max(1));
+    }
+    if let Some(reset_at) = resp
+        .headers()
+        .get("X-RateLimit-Reset")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+    {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        if reset_at > now {
+            return Duration::from_secs(reset_at - now);
+        }
+    }
+    Duration::from_secs(2u64.saturating_pow(attempt))
+}
+
+/// Issues a GET request, retrying with backoff if the response looks
+/// rate-limited, up to `max_retries` times. Any other status (success,
+/// 404, a genuine 403, ...) is returned straight to the caller to handle.
+pub fn send_with_retry(client: &Client, url: &str, max_retries: usize) -> Result<Response, Box<dyn Error>> {
+    let mut attempt = 0u32;
+    loop {
+        let resp = client.get(url).send()?;
+        if is_rate_limited(&resp) && (attempt as usize) < max_retries {
+            let wait = retry_delay(&resp, attempt);
+            eprintln!(
+                "Rate limited (HTTP {}); retrying {} in {}s ({}/{})",
+                resp.status(),
+                url,
+                wait.as_secs(),
+                attempt + 1,
+                max_retries
+            );
+            thread::sleep(wait);
+            attempt += 1;
+            continue;
+        }
+        return Ok(resp);
+    }
+}
+
+/// Extracts the `rel="next"` URL from a raw `Link` header value, for
+/// paginated listings (GitLab's tree endpoint, GitHub's Trees-API fallback).
+fn parse_next_link(link: &str) -> Option<String> {
+    for part in link.split(',') {
+        let mut fields = part.split(';');
+        let url_field = fields.next()?.trim();
+        if fields.any(|f| f.trim() == "rel=\"next\"") {
+            return Some(url_field.trim_start_matches('<').trim_end_matches('>').to_string());
+        }
+    }
+    None
+}
+
+/// Extracts the `rel="next"` URL from a response's `Link` header, if any.
+fn next_link(resp: &Response) -> Option<String> {
+    let link = resp.headers().get(reqwest::header::LINK)?.to_str().ok()?;
+    parse_next_link(link)
+}
+
+/// Figures out which backend a link's host maps to. `github.com`,
+/// `gitlab.com`, and `git.sr.ht` are recognized outright; any other host is
+/// only usable if the user has told us what it is via `GITHUB_API_URL`
+/// (GitHub Enterprise) or `GITLAB_HOST` (self-hosted GitLab).
+pub fn detect_host(url_host: &str) -> Result<HostKind, Box<dyn Error>> {
+    match url_host {
+        "github.com" => Ok(HostKind::GitHub),
+        "gitlab.com" => Ok(HostKind::GitLab),
+        "git.sr.ht" => Ok(HostKind::SourceHut),
+        other => {
+            if std::env::var("GITHUB_API_URL").is_ok() {
+                Ok(HostKind::GitHub)
+            } else if std::env::var("GITLAB_HOST").map(|h| h == other).unwrap_or(false) {
+                Ok(HostKind::GitLab)
+            } else {
+                Err(format!(
+                    "Unsupported host '{}'. For a GitHub Enterprise instance, set GITHUB_API_URL; for a self-hosted GitLab instance, set GITLAB_HOST={}.",
+                    other, other
+                )
+                .into())
+            }
+        }
+    }
+}
+
+/// Parses just enough of `link` to know which backend to build a client
+/// for, before the rest of the URL (owner/repo/ref/path) gets parsed.
+pub fn detect_host_from_link(link: &str) -> Result<HostKind, Box<dyn Error>> {
+    let url = Url::parse(link)?;
+    let url_host = url.host_str().ok_or("Invalid URL: missing host")?;
+    detect_host(url_host)
+}
+
+/// Resolves the API base URL to hit for `host`, honoring per-host
+/// environment overrides for self-hosted instances.
+pub fn default_api_base(host: &HostKind, url_host: &str) -> String {
+    match host {
+        HostKind::GitHub if url_host == "github.com" => {
+            std::env::var("GITHUB_API_URL").unwrap_or_else(|_| "https://api.github.com".to_string())
+        }
+        HostKind::GitHub => {
+            std::env::var("GITHUB_API_URL").unwrap_or_else(|_| format!("https://{}/api/v3", url_host))
+        }
+        HostKind::GitLab if url_host == "gitlab.com" => "https://gitlab.com/api/v4".to_string(),
+        HostKind::GitLab => format!("https://{}/api/v4", url_host),
+        HostKind::SourceHut => format!("https://{}", url_host),
+    }
+}
+
+/// GitLab addresses a project by its URL-encoded `namespace/name` path.
+/// Owners and repo names in practice never contain characters that need
+/// more than this, so a bare `%2F` join is enough.
+fn gitlab_project_id(owner: &str, repo: &str) -> String {
+    format!("{}%2F{}", owner, repo)
+}
+
+/// GitLab's per-file endpoints take the file path with its slashes encoded
+/// the same way.
+fn gitlab_encode_path(path: &str) -> String {
+    path.split('/').collect::<Vec<_>>().join("%2F")
+}
+
+fn gitlab_raw_url(api_base: &str, owner: &str, repo: &str, reference: &str, path: &str) -> String {
+    format!(
+        "{}/projects/{}/repository/files/{}/raw?ref={}",
+        api_base,
+        gitlab_project_id(owner, repo),
+        gitlab_encode_path(path),
+        reference
+    )
+}
+
+fn github_contents_url(api_base: &str, owner: &str, repo: &str, path: &str, reference: &str) -> String {
+    if path.is_empty() {
+        format!("{}/repos/{}/{}/contents?ref={}", api_base, owner, repo, reference)
+    } else {
+        format!("{}/repos/{}/{}/contents/{}?ref={}", api_base, owner, repo, path, reference)
+    }
+}
+
+/// GitHub's raw-content host for a given api base: `raw.githubusercontent.com`
+/// for github.com, or the Enterprise instance's own `/raw/` route.
+fn github_raw_url(api_base: &str, owner: &str, repo: &str, reference: &str, path: &str) -> String {
+    if api_base == "https://api.github.com" {
+        format!("https://raw.githubusercontent.com/{}/{}/{}/{}", owner, repo, reference, path)
+    } else {
+        let web_base = api_base.trim_end_matches("/api/v3");
+        format!("{}/{}/{}/raw/{}/{}", web_base, owner, repo, reference, path)
+    }
+}
+
+/// The Contents API's shape for one file or directory entry, shared by the
+/// directory-listing call (an array) and the single-file call (one object).
+#[derive(Deserialize)]
+struct GitHubContent {
+    name: String,
+    #[serde(rename = "type")]
+    r#type: String,
+    download_url: Option<String>,
+    sha: String,
+}
+
+/// The Contents API silently caps a directory listing at this many entries
+/// with no "truncated" flag to detect it by -- hitting the cap exactly is
+/// the only signal available, so that's the trigger for falling back to
+/// the Trees API.
+const GITHUB_CONTENTS_API_CAP: usize = 1000;
+
+/// Builds the tarball URL for a full-repo archive download. Only GitHub
+/// (including Enterprise) is supported here; GitLab and sourcehut repos are
+/// always walked entry-by-entry instead (see `fetch_tree` in `main.rs`).
+pub fn archive_url(api_base: &str, owner: &str, repo: &str, reference: &str) -> String {
+    if api_base == "https://api.github.com" {
+        format!("https://codeload.github.com/{}/{}/tar.gz/{}", owner, repo, reference)
+    } else {
+        let web_base = api_base.trim_end_matches("/api/v3");
+        format!("{}/{}/{}/archive/{}.tar.gz", web_base, owner, repo, reference)
+    }
+}
+
+/// A git ref is either a 40-hex-char commit sha (already pinned, nothing to
+/// resolve) or a branch/tag name (a moving pointer we need to resolve).
+fn is_full_sha(reference: &str) -> bool {
+    reference.len() == 40 && reference.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Resolves a branch/tag name to the commit sha it currently points at. A
+/// bare commit sha is returned unchanged since there's nothing to resolve.
+///
+/// sourcehut has no commits-by-ref endpoint to resolve a branch/tag with, so
+/// a non-sha reference there comes back as an empty string rather than an
+/// error -- the caller just won't get a pin recorded, instead of the whole
+/// download/refresh aborting over a cosmetic field.
+pub fn resolve_commit_sha(
+    client: &Client,
+    host: &HostKind,
+    api_base: &str,
+    owner: &str,
+    repo: &str,
+    reference: &str,
+    max_retries: usize,
+) -> Result<String, Box<dyn Error>> {
+    if is_full_sha(reference) {
+        return Ok(reference.to_string());
+    }
+    match host {
+        HostKind::GitHub => {
+            #[derive(Deserialize)]
+            struct CommitRef {
+                sha: String,
+            }
+            let url = format!("{}/repos/{}/{}/commits/{}", api_base, owner, repo, reference);
+            let resp = send_with_retry(client, &url, max_retries)?;
+            if !resp.status().is_success() {
+                return Err(format!("Failed to resolve ref '{}' to a commit: HTTP {}", reference, resp.status()).into());
+            }
+            Ok(resp.json::<CommitRef>()?.sha)
+        }
+        HostKind::GitLab => {
+            #[derive(Deserialize)]
+            struct CommitRef {
+                id: String,
+            }
+            let url = format!(
+                "{}/projects/{}/repository/commits/{}",
+                api_base,
+                gitlab_project_id(owner, repo),
+                reference
+            );
+            let resp = send_with_retry(client, &url, max_retries)?;
+            if !resp.status().is_success() {
+                return Err(format!("Failed to resolve ref '{}' to a commit: HTTP {}", reference, resp.status()).into());
+            }
+            Ok(resp.json::<CommitRef>()?.id)
+        }
+        HostKind::SourceHut => Ok(String::new()),
+    }
+}
+
+/// Resolves a repo's default branch name, for links that don't name a ref.
+pub fn resolve_default_branch(
+    client: &Client,
+    host: &HostKind,
+    api_base: &str,
+    owner: &str,
+    repo: &str,
+    max_retries: usize,
+) -> Result<String, Box<dyn Error>> {
+    match host {
+        HostKind::GitHub => {
+            #[derive(Deserialize)]
+            struct RepoInfo {
+                default_branch: String,
+            }
+            let url = format!("{}/repos/{}/{}", api_base, owner, repo);
+            let resp = send_with_retry(client, &url, max_retries)?;
+            if !resp.status().is_success() {
+                return Err(format!("Failed to look up default branch for {}/{}: HTTP {}", owner, repo, resp.status()).into());
+            }
+            Ok(resp.json::<RepoInfo>()?.default_branch)
+        }
+        HostKind::GitLab => {
+            #[derive(Deserialize)]
+            struct RepoInfo {
+                default_branch: String,
+            }
+            let url = format!("{}/projects/{}", api_base, gitlab_project_id(owner, repo));
+            let resp = send_with_retry(client, &url, max_retries)?;
+            if !resp.status().is_success() {
+                return Err(format!("Failed to look up default branch for {}/{}: HTTP {}", owner, repo, resp.status()).into());
+            }
+            Ok(resp.json::<RepoInfo>()?.default_branch)
+        }
+        HostKind::SourceHut => {
+            Err("A repo-root URL isn't supported for sourcehut; include an explicit /tree/ref or /blob/ref/path".into())
+        }
+    }
+}
+
+/// Lists the entries directly inside `path` (the repo root, if empty).
+pub fn list_dir(
+    client: &Client,
+    host: &HostKind,
+    api_base: &str,
+    owner: &str,
+    repo: &str,
+    reference: &str,
+    path: &str,
+    max_retries: usize,
+) -> Result<Vec<RemoteEntry>, Box<dyn Error>> {
+    match host {
+        HostKind::GitHub => {
+            let url = github_contents_url(api_base, owner, repo, path, reference);
+            let resp = send_with_retry(client, &url, max_retries)?;
+            match resp.status().as_u16() {
+                200 => {}
+                404 => return Err(Box::new(NotFound)),
+                403 => return Err(rate_limit_error(host)),
+                other => return Err(format!("Failed to list directory: HTTP {}", other).into()),
+            }
+            let items: Vec<GitHubContent> = resp.json()?;
+            if items.len() >= GITHUB_CONTENTS_API_CAP {
+                return github_list_dir_via_trees(client, api_base, owner, repo, reference, path, max_retries);
+            }
+            Ok(items
+                .into_iter()
+                .map(|item| {
+                    let sub_path = if path.is_empty() { item.name.clone() } else { format!("{}/{}", path, item.name) };
+                    RemoteEntry {
+                        is_dir: item.r#type == "dir",
+                        name: item.name,
+                        path: sub_path,
+                        sha: item.sha,
+                        download_url: item.download_url.unwrap_or_default(),
+                    }
+                })
+                .collect())
+        }
+        HostKind::GitLab => {
+            let mut page_url = Some(format!(
+                "{}/projects/{}/repository/tree?path={}&ref={}&per_page=100",
+                api_base,
+                gitlab_project_id(owner, repo),
+                path,
+                reference
+            ));
+            #[derive(Deserialize)]
+            struct TreeEntry {
+                id: String,
+                name: String,
+                #[serde(rename = "type")]
+                r#type: String,
+                path: String,
+            }
+            let mut entries = Vec::new();
+            while let Some(url) = page_url {
+                let resp = send_with_retry(client, &url, max_retries)?;
+                match resp.status().as_u16() {
+                    200 => {}
+                    404 => return Err(Box::new(NotFound)),
+                    403 => return Err(rate_limit_error(host)),
+                    other => return Err(format!("Failed to list directory: HTTP {}", other).into()),
+                }
+                page_url = next_link(&resp);
+                let items: Vec<TreeEntry> = resp.json()?;
+                entries.extend(items.into_iter().map(|item| {
+                    let is_dir = item.r#type == "tree";
+                    let download_url = if is_dir {
+                        String::new()
+                    } else {
+                        gitlab_raw_url(api_base, owner, repo, reference, &item.path)
+                    };
+                    RemoteEntry { name: item.name, path: item.path, is_dir, sha: item.id, download_url }
+                }));
+            }
+            Ok(entries)
+        }
+        HostKind::SourceHut => {
+            Err("Directory listing is not supported for sourcehut repositories yet; only single-file /blob/ links can be downloaded".into())
+        }
+    }
+}
+
+/// Given one entry's full repo-relative `item_path` from a recursive Trees
+/// API listing, works out which immediate child of `path` it falls under
+/// (or `None` if it's not under `path` at all), and whether that child is
+/// itself a directory -- either because the entry is nested deeper still,
+/// or because this entry is itself a tree. Returns `(child_name,
+/// child_path, is_dir)`.
+fn tree_item_child(item_path: &str, item_type_is_tree: bool, path: &str) -> Option<(String, String, bool)> {
+    let rest = if path.is_empty() {
+        item_path
+    } else {
+        item_path.strip_prefix(path).and_then(|r| r.strip_prefix('/'))?
+    };
+    let (child, is_dir) = match rest.split_once('/') {
+        Some((child, _)) => (child, true),
+        None => (rest, item_type_is_tree),
+    };
+    if child.is_empty() {
+        return None;
+    }
+    let child_path = if path.is_empty() { child.to_string() } else { format!("{}/{}", path, child) };
+    Some((child.to_string(), child_path, is_dir))
+}
+
+/// Falls back to the Git Trees API when a directory listing hit (or may
+/// have hit) the Contents API's 1,000-entry cap. `recursive=1` returns
+/// every entry in the whole repo tree in one (paginated) call, so this
+/// filters down to just the immediate children of `path` -- the same
+/// granularity `list_dir` normally returns -- and lets the existing
+/// work-queue recursion pick up subdirectories from there.
+fn github_list_dir_via_trees(
+    client: &Client,
+    api_base: &str,
+    owner: &str,
+    repo: &str,
+    reference: &str,
+    path: &str,
+    max_retries: usize,
+) -> Result<Vec<RemoteEntry>, Box<dyn Error>> {
+    #[derive(Deserialize)]
+    struct TreeItem {
+        path: String,
+        #[serde(rename = "type")]
+        r#type: String,
+        sha: String,
+    }
+    #[derive(Deserialize)]
+    struct TreeResponse {
+        tree: Vec<TreeItem>,
+        truncated: bool,
+    }
+
+    let mut page_url = Some(format!("{}/repos/{}/{}/git/trees/{}?recursive=1", api_base, owner, repo, reference));
+    let mut by_child: std::collections::HashMap<String, RemoteEntry> = std::collections::HashMap::new();
+    while let Some(url) = page_url {
+        let resp = send_with_retry(client, &url, max_retries)?;
+        match resp.status().as_u16() {
+            200 => {}
+            404 => return Err(Box::new(NotFound)),
+            403 => return Err(rate_limit_error(&HostKind::GitHub)),
+            other => return Err(format!("Failed to list directory via git trees: HTTP {}", other).into()),
+        }
+        page_url = next_link(&resp);
+        let body: TreeResponse = resp.json()?;
+        if body.truncated {
+            eprintln!(
+                "Warning: the git tree for {}/{} is truncated even via the recursive Trees API; some deeply-nested entries may be missing",
+                owner, repo
+            );
+        }
+        for item in body.tree {
+            let Some((child, child_path, is_dir)) = tree_item_child(&item.path, item.r#type == "tree", path) else {
+                continue;
+            };
+            if by_child.contains_key(&child) {
+                continue;
+            }
+            let download_url = if is_dir {
+                String::new()
+            } else {
+                github_raw_url(api_base, owner, repo, reference, &child_path)
+            };
+            by_child.insert(child.clone(), RemoteEntry {
+                name: child,
+                path: child_path,
+                is_dir,
+                sha: item.sha,
+                download_url,
+            });
+        }
+    }
+    Ok(by_child.into_values().collect())
+}
+
+/// Resolves the single file named by `path` (a `/blob/` URL).
+pub fn fetch_file_entry(
+    client: &Client,
+    host: &HostKind,
+    api_base: &str,
+    owner: &str,
+    repo: &str,
+    reference: &str,
+    path: &str,
+    max_retries: usize,
+) -> Result<RemoteEntry, Box<dyn Error>> {
+    match host {
+        HostKind::GitHub => {
+            let url = github_contents_url(api_base, owner, repo, path, reference);
+            let resp = send_with_retry(client, &url, max_retries)?;
+            match resp.status().as_u16() {
+                200 => {}
+                404 => return Err(Box::new(NotFound)),
+                403 => return Err(rate_limit_error(host)),
+                other => return Err(format!("Failed to fetch file: HTTP {}", other).into()),
+            }
+            let item: GitHubContent = resp.json()?;
+            let download_url = item.download_url.ok_or("File entry has no download_url")?;
+            Ok(RemoteEntry { name: item.name, path: path.to_string(), is_dir: false, sha: item.sha, download_url })
+        }
+        HostKind::GitLab => {
+            let url = format!(
+                "{}/projects/{}/repository/files/{}?ref={}",
+                api_base,
+                gitlab_project_id(owner, repo),
+                gitlab_encode_path(path),
+                reference
+            );
+            let resp = send_with_retry(client, &url, max_retries)?;
+            match resp.status().as_u16() {
+                200 => {}
+                404 => return Err(Box::new(NotFound)),
+                403 => return Err(rate_limit_error(host)),
+                other => return Err(format!("Failed to fetch file: HTTP {}", other).into()),
+            }
+            #[derive(Deserialize)]
+            struct FileInfo {
+                file_name: String,
+                blob_id: String,
+            }
+            let info: FileInfo = resp.json()?;
+            let download_url = gitlab_raw_url(api_base, owner, repo, reference, path);
+            Ok(RemoteEntry { name: info.file_name, path: path.to_string(), is_dir: false, sha: info.blob_id, download_url })
+        }
+        HostKind::SourceHut => {
+            // sourcehut has no Contents-style metadata endpoint, so there's no
+            // sha to report up front; `fetch_single_file` hashes the
+            // downloaded bytes itself instead, the same way `download_archive`
+            // already does for tarball entries.
+            let name = path.rsplit('/').next().unwrap_or(path).to_string();
+            let download_url = format!("{}/~{}/{}/blob/{}/{}", api_base, owner, repo, reference, path);
+            Ok(RemoteEntry { name, path: path.to_string(), is_dir: false, sha: String::new(), download_url })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_full_sha_accepts_only_40_hex_chars() {
+        assert!(is_full_sha(&"a".repeat(40)));
+        assert!(!is_full_sha(&"a".repeat(39)));
+        assert!(!is_full_sha("main"));
+        assert!(!is_full_sha("v1.0.0"));
+    }
+
+    #[test]
+    fn tree_item_child_direct_child_file() {
+        let (child, child_path, is_dir) = tree_item_child("src/main.rs", false, "src").unwrap();
+        assert_eq!(child, "main.rs");
+        assert_eq!(child_path, "src/main.rs");
+        assert!(!is_dir);
+    }
+
+    #[test]
+    fn tree_item_child_nested_entry_surfaces_intermediate_dir() {
+        let (child, child_path, is_dir) = tree_item_child("src/sub/deep.rs", false, "src").unwrap();
+        assert_eq!(child, "sub");
+        assert_eq!(child_path, "src/sub");
+        assert!(is_dir);
+    }
+
+    #[test]
+    fn tree_item_child_repo_root() {
+        let (child, child_path, is_dir) = tree_item_child("README.md", false, "").unwrap();
+        assert_eq!(child, "README.md");
+        assert_eq!(child_path, "README.md");
+        assert!(!is_dir);
+    }
+
+    #[test]
+    fn tree_item_child_outside_requested_path_is_skipped() {
+        assert!(tree_item_child("other/file.rs", false, "src").is_none());
+    }
+
+    #[test]
+    fn tree_item_child_empty_tree_entry_for_requested_path_is_skipped() {
+        assert!(tree_item_child("src", true, "src").is_none());
+    }
+
+    #[test]
+    fn parse_next_link_finds_rel_next_among_other_relations() {
+        let header = r#"<https://api.example.com/trees?page=2>; rel="next", <https://api.example.com/trees?page=1>; rel="prev""#;
+        assert_eq!(parse_next_link(header).as_deref(), Some("https://api.example.com/trees?page=2"));
+    }
+
+    #[test]
+    fn parse_next_link_returns_none_without_a_next_relation() {
+        let header = r#"<https://api.example.com/trees?page=1>; rel="prev""#;
+        assert!(parse_next_link(header).is_none());
+    }
+}
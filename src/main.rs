@@ -1,18 +1,35 @@
 use std::error::Error;
 use std::fs;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 use clap::{Parser, Subcommand};
 // Load environment variables from .env (e.g., GITHUB_TOKEN)
 use dotenvy::dotenv;
-use std::sync::Arc;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
 use serde::{Serialize, Deserialize};
 use reqwest::blocking::Client;
 use reqwest::header::AUTHORIZATION;
 use url::Url;
+use flate2::read::GzDecoder;
+use tar::Archive as TarArchive;
+use sha1::{Digest, Sha1};
+
+mod host;
+use host::HostKind;
+
+/// Default number of concurrent download workers when `--jobs` is not given.
+const DEFAULT_JOBS: usize = 8;
+
+/// Default number of times to retry a request that looks rate-limited
+/// before giving up, when `--max-retries` is not given.
+const DEFAULT_MAX_RETRIES: usize = 5;
 
 #[derive(Parser)]
 #[command(name = "github-dl")]
-#[command(about = "Download GitHub folders", long_about = None)]
+#[command(about = "Download folders from GitHub, GitLab, or sourcehut", long_about = None)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
@@ -20,29 +37,140 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Download a GitHub folder
+    /// Download a folder from GitHub, GitLab, or sourcehut
     Download {
-        /// GitHub folder URL (e.g., https://github.com/owner/repo/tree/ref/path)
+        /// Repo folder URL (e.g., https://github.com/owner/repo/tree/ref/path)
         link: String,
         /// Output directory to save the folder
         #[arg(short, long)]
         output: PathBuf,
+        /// Maximum number of files/directories to download concurrently
+        #[arg(short, long, default_value_t = DEFAULT_JOBS)]
+        jobs: usize,
+        /// Fetch strategy: one Contents API call per file/dir, one tarball
+        /// download for the whole repo, or auto-pick based on what's requested
+        #[arg(short, long, value_enum, default_value_t = DownloadMode::Auto)]
+        mode: DownloadMode,
+        /// Maximum number of times to retry a rate-limited request, with
+        /// exponential backoff, before giving up
+        #[arg(long, default_value_t = DEFAULT_MAX_RETRIES)]
+        max_retries: usize,
     },
     /// Refresh all downloaded folders in the base directory
     Refresh {
         /// Base directory to search for downloaded folders [default: current directory]
         #[arg(short, long, default_value = ".")]
         base_dir: PathBuf,
+        /// Maximum number of files/directories to download concurrently
+        #[arg(short, long, default_value_t = DEFAULT_JOBS)]
+        jobs: usize,
+        /// Fetch strategy: one Contents API call per file/dir, one tarball
+        /// download for the whole repo, or auto-pick based on what's requested
+        #[arg(short, long, value_enum, default_value_t = DownloadMode::Auto)]
+        mode: DownloadMode,
+        /// Fetch the exact commit recorded at the last download/update instead
+        /// of following the branch/tag, so the refresh is reproducible
+        #[arg(long)]
+        pin: bool,
+        /// Maximum number of times to retry a rate-limited request, with
+        /// exponential backoff, before giving up
+        #[arg(long, default_value_t = DEFAULT_MAX_RETRIES)]
+        max_retries: usize,
+    },
+    /// Advance a downloaded folder's pinned commit to the current branch head
+    Update {
+        /// Base directory to search for downloaded folders [default: current directory]
+        #[arg(short, long, default_value = ".")]
+        base_dir: PathBuf,
+        /// Maximum number of files/directories to download concurrently
+        #[arg(short, long, default_value_t = DEFAULT_JOBS)]
+        jobs: usize,
+        /// Fetch strategy: one Contents API call per file/dir, one tarball
+        /// download for the whole repo, or auto-pick based on what's requested
+        #[arg(short, long, value_enum, default_value_t = DownloadMode::Auto)]
+        mode: DownloadMode,
+        /// Maximum number of times to retry a rate-limited request, with
+        /// exponential backoff, before giving up
+        #[arg(long, default_value_t = DEFAULT_MAX_RETRIES)]
+        max_retries: usize,
     },
 }
 
+/// Which strategy to use for fetching a repo subtree.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum DownloadMode {
+    /// Walk directories via the Contents API, one request per file/dir.
+    Api,
+    /// Download the whole repo as a tarball and extract the requested subtree in one request.
+    Archive,
+    /// Archive mode when the whole repo is requested, Api mode for a subfolder.
+    Auto,
+}
+
+/// Resolves `Auto` to a concrete strategy: for a first-time download, a
+/// full repo always benefits from one tarball request, while a single
+/// subfolder is normally cheaper to fetch directly since downloading and
+/// decompressing the whole tarball just to keep a few entries would be
+/// wasteful. A refresh is different: the archive has no per-file sha to
+/// skip against, so it always re-downloads and re-extracts everything,
+/// defeating the incremental refresh the Contents API path gets for free.
+/// `Auto` therefore prefers `Api` whenever `is_refresh` is set, whole repo
+/// or not.
+fn resolve_mode(mode: DownloadMode, path: &str, is_refresh: bool) -> DownloadMode {
+    match mode {
+        DownloadMode::Auto if is_refresh => DownloadMode::Api,
+        DownloadMode::Auto => {
+            if path.is_empty() {
+                DownloadMode::Archive
+            } else {
+                DownloadMode::Api
+            }
+        }
+        other => other,
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 struct Metadata {
     owner: String,
     repo: String,
+    /// The branch, tag, or sha the user asked for (may be a moving branch).
     reference: String,
+    /// The commit `reference` resolved to as of the last download/update.
+    /// Empty for metadata written before this field existed, and for hosts
+    /// (sourcehut) that have no way to resolve a branch/tag to a commit.
+    #[serde(default)]
+    commit: String,
     path: String,
     url: String,
+    /// True if `path` names a single file (a `/blob/` URL) rather than a directory.
+    #[serde(default)]
+    is_blob: bool,
+    /// Which forge this repo lives on. Absent (defaulting to GitHub) in
+    /// files written before multi-host support existed.
+    #[serde(default)]
+    host: HostKind,
+    /// The API base URL for `host`, e.g. `https://api.github.com` or a
+    /// GitHub Enterprise/self-hosted GitLab instance's own API root.
+    #[serde(default)]
+    api_base: String,
+    /// Git blob sha of every downloaded file, keyed by path relative to the
+    /// local output directory, as of the last successful download/refresh.
+    /// Lets a refresh compare against the remote sha without re-hashing
+    /// unchanged files. Absent in files written before this field existed.
+    #[serde(default)]
+    file_shas: HashMap<String, String>,
+}
+
+/// Computes the git blob object hash for file content: `sha1("blob "
+/// + len + "\0" + content)`. This is exactly the `sha` the Contents API
+/// reports for a file, so it lets us compare local content against a
+/// remote entry without downloading it.
+fn git_blob_sha1(content: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(format!("blob {}\0", content.len()));
+    hasher.update(content);
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
 }
 
 fn main() {
@@ -56,33 +184,47 @@ fn run() -> Result<(), Box<dyn Error>> {
     // Load .env file into environment (e.g., GITHUB_TOKEN)
     dotenv().ok();
     let cli = Cli::parse();
-    // Build HTTP client and wrap in Arc for thread-safe sharing
-    let client = Arc::new(build_client()?);
 
     match cli.command {
-        Commands::Download { link, output } => {
-            let (parsed, _) = parse_github_link(&link)?;
+        Commands::Download { link, output, jobs, mode, max_retries } => {
+            // The right credentials to send depend on which forge the link
+            // points at, so the client can't be built until that's known.
+            let host_kind = host::detect_host_from_link(&link)?;
+            let client = Arc::new(build_client(&host_kind)?);
+            let (host, api_base, parsed) = parse_repo_link(&client, &link, max_retries)?;
             if output.exists() {
                 if output.read_dir()?.next().is_some() {
                     return Err(format!("Output directory '{}' is not empty", output.display()).into());
                 }
             }
             fs::create_dir_all(&output)?;
+            let commit = host::resolve_commit_sha(&client, &host, &api_base, &parsed.owner, &parsed.repo, &parsed.reference, max_retries)?;
             let meta = Metadata {
                 owner: parsed.owner,
                 repo: parsed.repo,
                 reference: parsed.reference,
+                commit,
                 path: parsed.path,
                 url: link.clone(),
+                is_blob: parsed.is_blob,
+                host,
+                api_base,
+                file_shas: HashMap::new(),
             };
             let meta_path = output.join(".github-dl.json");
-            let meta_json = serde_json::to_string_pretty(&meta)?;
-            fs::write(&meta_path, meta_json)?;
+            fs::write(&meta_path, serde_json::to_string_pretty(&meta)?)?;
             // Perform download
-            download_dir(&client, &meta, &output)?;
-            println!("Downloaded to {}", output.display());
+            let (file_shas, errors) = if meta.is_blob {
+                (fetch_single_file(&client, &meta, &output, max_retries)?, Vec::new())
+            } else {
+                fetch_tree(&client, &meta, &output, jobs, mode, &meta.file_shas, max_retries, false)?
+            };
+            println!("Downloaded to {} ({} files)", output.display(), file_shas.len());
+            report_errors(&errors);
+            let meta = Metadata { file_shas, ..meta };
+            fs::write(&meta_path, serde_json::to_string_pretty(&meta)?)?;
         }
-        Commands::Refresh { base_dir } => {
+        Commands::Refresh { base_dir, jobs, mode, pin, max_retries } => {
             let mut metas = Vec::new();
             find_metadata_files(&base_dir, &mut metas)?;
             if metas.is_empty() {
@@ -90,42 +232,20 @@ fn run() -> Result<(), Box<dyn Error>> {
                 return Ok(());
             }
             for meta_file in &metas {
-                let meta_str = fs::read_to_string(&meta_file)?;
-                let meta: Metadata = serde_json::from_str(&meta_str)?;
-                let meta = Arc::new(meta);
-                println!("Refreshing '{}'", meta.url);
-                let listing_url = if meta.path.is_empty() {
-                    format!("https://api.github.com/repos/{}/{}/contents?ref={}", meta.owner, meta.repo, meta.reference)
-                } else {
-                    format!("https://api.github.com/repos/{}/{}/contents/{}?ref={}", meta.owner, meta.repo, meta.path, meta.reference)
-                };
-                // Check if remote folder exists or we have permission to list it
-                let resp = client.get(&listing_url).send()?;
-                let status = resp.status();
-                if status.as_u16() == 404 {
-                    eprintln!("Remote folder {} does not exist, skipping", meta.url);
-                    continue;
-                } else if status.as_u16() == 403 {
-                    return Err("Failed to list directory: HTTP 403 Forbidden. Are you hitting the GitHub API rate limit? Try setting the GITHUB_TOKEN environment variable.".into());
-                } else if !status.is_success() {
-                    return Err(format!("Failed to list directory: HTTP {}", status).into());
-                }
-                let local_dir = meta_file.parent().unwrap().to_path_buf();
-                for entry in fs::read_dir(&local_dir)? {
-                    let entry = entry?;
-                    let path = entry.path();
-                    if path.file_name() == Some(std::ffi::OsStr::new(".github-dl.json")) {
-                        continue;
-                    }
-                    if path.is_dir() {
-                        fs::remove_dir_all(&path)?;
-                    } else {
-                        fs::remove_file(&path)?;
-                    }
-                }
-                // Refresh contents
-                download_dir(&client, &meta, &local_dir)?;
-                println!("Refreshed '{}'", meta.url);
+                refresh_folder(meta_file, jobs, mode, pin, max_retries)?;
+            }
+        }
+        Commands::Update { base_dir, jobs, mode, max_retries } => {
+            let mut metas = Vec::new();
+            find_metadata_files(&base_dir, &mut metas)?;
+            if metas.is_empty() {
+                println!("No downloaded folders found in {}", base_dir.display());
+                return Ok(());
+            }
+            for meta_file in &metas {
+                // An update always follows the branch/tag head and advances the pin,
+                // regardless of whether the folder is normally kept pinned.
+                refresh_folder(meta_file, jobs, mode, false, max_retries)?;
             }
         }
     }
@@ -133,103 +253,656 @@ fn run() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Refreshes one previously-downloaded folder in place: fetches either the
+/// pinned commit (`pin`) or the current branch/tag head, applies the diff,
+/// and rewrites `.github-dl.json` with the new shas and resolved commit.
+fn refresh_folder(meta_file: &Path, jobs: usize, mode: DownloadMode, pin: bool, max_retries: usize) -> Result<(), Box<dyn Error>> {
+    let meta_str = fs::read_to_string(meta_file)?;
+    let meta: Metadata = serde_json::from_str(&meta_str)?;
+    println!("Refreshing '{}'", meta.url);
+    let client = Arc::new(build_client(&meta.host)?);
+
+    let fetch_ref = if pin {
+        if meta.commit.is_empty() {
+            return Err(format!("'{}' has no pinned commit recorded; run a plain refresh or update first", meta.url).into());
+        }
+        meta.commit.clone()
+    } else {
+        meta.reference.clone()
+    };
+    let fetch_meta = Metadata { reference: fetch_ref.clone(), ..meta.clone() };
+
+    let local_dir = meta_file.parent().unwrap().to_path_buf();
+    // Incremental refresh: compare each entry's remote sha against what
+    // we recorded last time instead of wiping and re-downloading everything.
+    let fetch_result: Result<(HashMap<String, String>, Vec<DownloadError>), Box<dyn Error>> = if meta.is_blob {
+        fetch_single_file(&client, &fetch_meta, &local_dir, max_retries).map(|shas| (shas, Vec::new()))
+    } else {
+        fetch_tree(&client, &fetch_meta, &local_dir, jobs, mode, &meta.file_shas, max_retries, true)
+    };
+    let (new_shas, errors) = match fetch_result {
+        Err(err) if err.downcast_ref::<host::NotFound>().is_some() => {
+            eprintln!("Remote folder {} does not exist, skipping", meta.url);
+            return Ok(());
+        }
+        Err(err) => return Err(err),
+        Ok(result) => result,
+    };
+    // Only prune local files that are genuinely gone remotely. If anything
+    // failed to fetch, `new_shas` is missing entries for reasons other than
+    // removal, so deleting based on absence there would destroy good local
+    // copies of files that merely hit a transient error. Carry those
+    // entries forward unchanged instead of losing track of them, so a
+    // later clean refresh can still prune them if they really are gone.
+    let (new_shas, deleted) = if errors.is_empty() {
+        let deleted = prune_removed_files(&local_dir, &meta.file_shas, &new_shas)?;
+        (new_shas, deleted)
+    } else {
+        eprintln!("Skipping deletion of locally-removed files for '{}': {} item(s) failed to fetch", meta.url, errors.len());
+        let mut new_shas = new_shas;
+        for (path, sha) in &meta.file_shas {
+            new_shas.entry(path.clone()).or_insert_with(|| sha.clone());
+        }
+        (new_shas, 0)
+    };
+    let (added, updated, unchanged) = diff_shas(&meta.file_shas, &new_shas);
+    println!(
+        "Refreshed '{}': {} added, {} updated, {} deleted, {} unchanged",
+        meta.url, added, updated, deleted, unchanged
+    );
+    report_errors(&errors);
+
+    let commit = if pin {
+        meta.commit.clone()
+    } else {
+        host::resolve_commit_sha(&client, &meta.host, &meta.api_base, &meta.owner, &meta.repo, &meta.reference, max_retries)?
+    };
+    let updated_meta = Metadata { file_shas: new_shas, commit, ..meta };
+    fs::write(meta_file, serde_json::to_string_pretty(&updated_meta)?)?;
+    Ok(())
+}
+
 struct ParsedLink {
     owner: String,
     repo: String,
     reference: String,
     path: String,
+    /// True for a `/blob/` URL naming a single file rather than a directory.
+    is_blob: bool,
 }
 
-fn parse_github_link(link: &str) -> Result<(ParsedLink, Url), Box<dyn Error>> {
+/// Parses a repo URL in any of these forms:
+/// - GitHub/GitHub Enterprise: `https://github.com/owner/repo[/tree/ref[/path]]`
+///   or `https://github.com/owner/repo/blob/ref/path/to/file`
+/// - GitLab: `https://gitlab.com/owner/repo[/-/tree/ref[/path]]` or `.../-/blob/ref/path`
+/// - sourcehut: `https://git.sr.ht/~owner/repo/blob/ref/path` (directory
+///   browsing isn't supported, so a repo-root or `/tree/` link won't resolve)
+///
+/// A repo-root link (no `/tree/` or `/blob/`) resolves the default branch.
+/// `ref` may be a branch, a tag, or a bare 40-character commit sha.
+fn parse_repo_link(client: &Client, link: &str, max_retries: usize) -> Result<(HostKind, String, ParsedLink), Box<dyn Error>> {
     let url = Url::parse(link)?;
-    let host = url.host_str().ok_or("Invalid URL: missing host")?;
-    if host != "github.com" {
-        return Err("URL is not a github.com link".into());
-    }
-    let segments: Vec<_> = url.path_segments().ok_or("Cannot parse URL path segments")?.collect();
-    if segments.len() < 4 || segments[2] != "tree" {
-        return Err("URL must be in the format https://github.com/owner/repo/tree/ref[/path]".into());
-    }
-    let owner = segments[0].to_string();
-    let repo = segments[1].to_string();
-    let reference = segments[3].to_string();
-    let path = if segments.len() > 4 {
-        segments[4..].join("/")
-    } else {
-        String::new()
+    let url_host = url.host_str().ok_or("Invalid URL: missing host")?;
+    let host = host::detect_host(url_host)?;
+    let api_base = host::default_api_base(&host, url_host);
+    let segments: Vec<_> = url
+        .path_segments()
+        .ok_or("Cannot parse URL path segments")?
+        .filter(|s| !s.is_empty())
+        .collect();
+    const USAGE: &str = "URL must be in the format https://github.com/owner/repo[/tree/ref[/path]], https://gitlab.com/owner/repo[/-/tree/ref[/path]], or https://git.sr.ht/~owner/repo/blob/ref/path";
+    if segments.len() < 2 {
+        return Err(USAGE.into());
+    }
+
+    // GitLab inserts a literal `-` marker segment before `tree`/`blob`;
+    // sourcehut prefixes the owner with `~`. Everything else lines up with
+    // GitHub's `owner/repo/tree-or-blob/ref/path` shape.
+    let (owner, repo, rest): (String, String, &[&str]) = match host {
+        HostKind::SourceHut => {
+            let owner = segments[0].strip_prefix('~').ok_or(USAGE)?.to_string();
+            (owner, segments[1].to_string(), &segments[2..])
+        }
+        HostKind::GitLab if segments.get(2).copied() == Some("-") => {
+            (segments[0].to_string(), segments[1].to_string(), &segments[3..])
+        }
+        _ => (segments[0].to_string(), segments[1].to_string(), &segments[2..]),
     };
-    Ok((ParsedLink { owner, repo, reference, path }, url))
+
+    if rest.is_empty() {
+        let reference = host::resolve_default_branch(client, &host, &api_base, &owner, &repo, max_retries)?;
+        let parsed = ParsedLink { owner, repo, reference, path: String::new(), is_blob: false };
+        return Ok((host, api_base, parsed));
+    }
+
+    if rest.len() < 2 || (rest[0] != "tree" && rest[0] != "blob") {
+        return Err(USAGE.into());
+    }
+    let is_blob = rest[0] == "blob";
+    let reference = rest[1].to_string();
+    let path = if rest.len() > 2 { rest[2..].join("/") } else { String::new() };
+    if is_blob && path.is_empty() {
+        return Err("A /blob/ URL must include a file path".into());
+    }
+    let parsed = ParsedLink { owner, repo, reference, path, is_blob };
+    Ok((host, api_base, parsed))
 }
 
-fn build_client() -> Result<Client, Box<dyn Error>> {
+/// Builds the HTTP client with whichever credential `host` expects: a
+/// `token` bearer for GitHub, a `PRIVATE-TOKEN` header for GitLab, or a
+/// plain `Bearer` token for sourcehut.
+fn build_client(host: &HostKind) -> Result<Client, Box<dyn Error>> {
     let mut builder = Client::builder().user_agent("github-dl");
-    if let Ok(token) = std::env::var("GITHUB_TOKEN") {
-        let mut headers = reqwest::header::HeaderMap::new();
-        let value = format!("token {}", token);
-        headers.insert(AUTHORIZATION, value.parse()?);
+    let mut headers = reqwest::header::HeaderMap::new();
+    match host {
+        HostKind::GitHub => {
+            if let Ok(token) = std::env::var("GITHUB_TOKEN") {
+                headers.insert(AUTHORIZATION, format!("token {}", token).parse()?);
+            }
+        }
+        HostKind::GitLab => {
+            if let Ok(token) = std::env::var("GITLAB_TOKEN") {
+                headers.insert("PRIVATE-TOKEN", token.parse()?);
+            }
+        }
+        HostKind::SourceHut => {
+            if let Ok(token) = std::env::var("SRHT_TOKEN") {
+                headers.insert(AUTHORIZATION, format!("Bearer {}", token).parse()?);
+            }
+        }
+    }
+    if !headers.is_empty() {
         builder = builder.default_headers(headers);
     }
     Ok(builder.build()?)
 }
 
-fn download_dir(client: &Client, meta: &Metadata, local_path: &Path) -> Result<(), Box<dyn Error>> {
+/// An error downloading a single file or listing a single directory.
+///
+/// Individual failures are collected rather than aborting the whole
+/// download so that one missing/forbidden file doesn't throw away an
+/// otherwise-successful run.
+struct DownloadError {
+    path: String,
+    message: String,
+}
+
+fn report_errors(errors: &[DownloadError]) {
+    if errors.is_empty() {
+        return;
+    }
+    eprintln!("{} item(s) failed to download:", errors.len());
+    for err in errors {
+        eprintln!("  {}: {}", err.path, err.message);
+    }
+}
+
+/// A unit of work discovered while walking a directory tree.
+enum WorkItem {
+    Dir { meta: Metadata, local_path: PathBuf },
+    File { download_url: String, local_path: PathBuf, sha: String },
+}
+
+/// State shared by the worker pool while syncing a tree: where it's rooted
+/// locally, the shas recorded by the previous download/refresh, and the
+/// shas actually present on disk once this run finishes.
+struct SyncContext {
+    local_root: PathBuf,
+    old_shas: HashMap<String, String>,
+    new_shas: Mutex<HashMap<String, String>>,
+    max_retries: usize,
+}
+
+fn relative_to_root(local_root: &Path, local_path: &Path) -> String {
+    local_path
+        .strip_prefix(local_root)
+        .unwrap_or(local_path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// Computes added/updated/unchanged counts by comparing the shas recorded
+/// before this run against the shas recorded after it. Deletions are
+/// counted separately by [`prune_removed_files`], since that's the step
+/// that actually knows which files it removed.
+fn diff_shas(old_shas: &HashMap<String, String>, new_shas: &HashMap<String, String>) -> (usize, usize, usize) {
+    let mut added = 0;
+    let mut updated = 0;
+    let mut unchanged = 0;
+    for (path, sha) in new_shas {
+        match old_shas.get(path) {
+            None => added += 1,
+            Some(old_sha) if old_sha == sha => unchanged += 1,
+            Some(_) => updated += 1,
+        }
+    }
+    (added, updated, unchanged)
+}
+
+/// Removes local files that were present last time but no longer appear in
+/// the freshly-fetched tree, then cleans up any directories left empty.
+fn prune_removed_files(local_root: &Path, old_shas: &HashMap<String, String>, new_shas: &HashMap<String, String>) -> std::io::Result<usize> {
+    let mut deleted = 0;
+    for rel_path in old_shas.keys() {
+        if new_shas.contains_key(rel_path) {
+            continue;
+        }
+        let full_path = local_root.join(rel_path);
+        if full_path.is_file() {
+            fs::remove_file(&full_path)?;
+            deleted += 1;
+            if let Some(parent) = full_path.parent() {
+                remove_empty_ancestors(parent, local_root);
+            }
+        }
+    }
+    Ok(deleted)
+}
+
+/// Removes `dir` and any now-empty ancestors up to (but not including)
+/// `stop_at`, stopping at the first non-empty directory.
+fn remove_empty_ancestors(mut dir: &Path, stop_at: &Path) {
+    while dir != stop_at {
+        match fs::read_dir(dir) {
+            Ok(mut entries) if entries.next().is_none() => {
+                if fs::remove_dir(dir).is_err() {
+                    return;
+                }
+            }
+            _ => return,
+        }
+        match dir.parent() {
+            Some(parent) => dir = parent,
+            None => return,
+        }
+    }
+}
+
+/// A bounded work queue shared by the download worker pool.
+///
+/// `pending` tracks every item that has been pushed but not yet finished
+/// processing (including items currently being worked on), so workers know
+/// to keep waiting for more work rather than exiting as soon as the queue
+/// is briefly empty.
+struct WorkQueue {
+    items: Mutex<VecDeque<WorkItem>>,
+    cond: Condvar,
+    pending: AtomicUsize,
+}
+
+impl WorkQueue {
+    fn new() -> Self {
+        WorkQueue {
+            items: Mutex::new(VecDeque::new()),
+            cond: Condvar::new(),
+            pending: AtomicUsize::new(0),
+        }
+    }
+
+    fn push(&self, item: WorkItem) {
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        self.items.lock().unwrap().push_back(item);
+        self.cond.notify_one();
+    }
+
+    /// Blocks until an item is available or there is no work left anywhere,
+    /// in which case it returns `None` and the calling worker can exit.
+    fn pop(&self) -> Option<WorkItem> {
+        let mut guard = self.items.lock().unwrap();
+        loop {
+            if let Some(item) = guard.pop_front() {
+                return Some(item);
+            }
+            if self.pending.load(Ordering::SeqCst) == 0 {
+                return None;
+            }
+            guard = self.cond.wait(guard).unwrap();
+        }
+    }
+
+    /// Marks one previously-pushed item as finished. Must be called exactly
+    /// once per `push`, after any children it enqueued have themselves been
+    /// pushed.
+    ///
+    /// The decrement has to happen under `items`'s lock, the same one
+    /// `pop` holds while checking `pending` and calling `cond.wait`.
+    /// Otherwise a decrement that lands between `pop`'s `pending.load()`
+    /// and its `cond.wait` is a wakeup with nobody listening yet: the
+    /// worker then waits forever on a queue that will never get another
+    /// push or notify.
+    fn complete_one(&self) {
+        let guard = self.items.lock().unwrap();
+        self.pending.fetch_sub(1, Ordering::SeqCst);
+        drop(guard);
+        self.cond.notify_all();
+    }
+}
+
+/// Fetches `meta.path` into `local_path` using whichever strategy `mode`
+/// resolves to (see [`resolve_mode`]), comparing against `old_shas` so
+/// unchanged files can be skipped. Returns the git blob sha recorded for
+/// every file now on disk, alongside any per-item errors.
+#[allow(clippy::too_many_arguments)]
+fn fetch_tree(client: &Arc<Client>, meta: &Metadata, local_path: &Path, jobs: usize, mode: DownloadMode, old_shas: &HashMap<String, String>, max_retries: usize, is_refresh: bool) -> Result<(HashMap<String, String>, Vec<DownloadError>), Box<dyn Error>> {
+    // Tarball archives only exist for GitHub (codeload/the `/archive/` web
+    // route); other forges are always walked entry-by-entry.
+    if meta.host != HostKind::GitHub {
+        return download_dir(client, meta, local_path, jobs, old_shas, max_retries);
+    }
+    match resolve_mode(mode, &meta.path, is_refresh) {
+        DownloadMode::Archive => Ok((download_archive(client, meta, local_path, max_retries)?, Vec::new())),
+        DownloadMode::Api => download_dir(client, meta, local_path, jobs, old_shas, max_retries),
+        DownloadMode::Auto => unreachable!("resolve_mode never returns Auto"),
+    }
+}
+
+/// Fetches the single file named by `meta.path` (a `/blob/` URL) into
+/// `local_path`, for a repo/refresh whose `is_blob` is set.
+fn fetch_single_file(client: &Client, meta: &Metadata, local_path: &Path, max_retries: usize) -> Result<HashMap<String, String>, Box<dyn Error>> {
     fs::create_dir_all(local_path)?;
-    let listing_url = if meta.path.is_empty() {
-        format!("https://api.github.com/repos/{}/{}/contents?ref={}", meta.owner, meta.repo, meta.reference)
-    } else {
-        format!("https://api.github.com/repos/{}/{}/contents/{}?ref={}", meta.owner, meta.repo, meta.path, meta.reference)
-    };
-    // Request directory listing; handle possible rate limiting
-    let resp = client.get(&listing_url).send()?;
+    let entry = host::fetch_file_entry(client, &meta.host, &meta.api_base, &meta.owner, &meta.repo, &meta.reference, &meta.path, max_retries)?;
+    let dest = local_path.join(&entry.name);
+    download_file(client, &entry.download_url, &dest, max_retries)?;
+    let mut file_shas = HashMap::new();
+    file_shas.insert(entry.name, git_blob_sha1(&fs::read(&dest)?));
+    Ok(file_shas)
+}
+
+/// Strips the single top-level directory codeload wraps every tarball entry
+/// in. That directory is *not* reliably `<repo>-<ref>/`: a `v`-prefixed tag
+/// has its leading `v` dropped (`v2.0.0` -> `repo-2.0.0/`) and a branch name
+/// containing `/` has its slashes turned into `-`, so reconstructing the
+/// name from `repo`/`reference` silently matches nothing for those refs.
+/// Stripping whatever the first path component actually is works regardless
+/// of how codeload named it.
+fn strip_archive_root(entry_path: &str) -> Option<&str> {
+    entry_path.split_once('/').map(|(_, rest)| rest)
+}
+
+/// Downloads the whole repo as a gzipped tarball from codeload and extracts
+/// only the entries under `meta.path`, stripping the top-level directory
+/// GitHub wraps every entry in (see [`strip_archive_root`]). This costs a
+/// single HTTP request no matter how many files the subtree contains.
+/// Always re-extracts every matching entry (there's no per-file request to
+/// skip), but still records each file's git blob sha so a later Api-mode
+/// refresh can diff against it.
+fn download_archive(client: &Client, meta: &Metadata, local_path: &Path, max_retries: usize) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    fs::create_dir_all(local_path)?;
+    let archive_url = host::archive_url(&meta.api_base, &meta.owner, &meta.repo, &meta.reference);
+    let resp = host::send_with_retry(client, &archive_url, max_retries)?;
     let status = resp.status();
     if status.as_u16() == 403 {
-        return Err("Failed to list directory: HTTP 403 Forbidden. Are you hitting the GitHub API rate limit? Try setting the GITHUB_TOKEN environment variable.".into());
+        return Err("Failed to download archive: HTTP 403 Forbidden. Are you hitting the GitHub API rate limit? Try setting the GITHUB_TOKEN environment variable, or raise --max-retries.".into());
     } else if !status.is_success() {
-        return Err(format!("Failed to list directory: HTTP {}", status).into());
-    }
-    let items: Vec<Content> = resp.json()?;
-    for item in items {
-        let name = &item.name;
-        let local_file_path = local_path.join(name);
-        match item.r#type.as_str() {
-            "file" => {
-                if let Some(dl_url) = item.download_url {
-                    let resp_file = client.get(&dl_url).send()?;
-                    if !resp_file.status().is_success() {
-                        return Err(format!("Failed to download file {}: HTTP {}", dl_url, resp_file.status()).into());
+        return Err(format!("Failed to download archive: HTTP {}", status).into());
+    }
+
+    let mut tar = TarArchive::new(GzDecoder::new(resp));
+    let mut file_shas = HashMap::new();
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.to_string_lossy().into_owned();
+        let Some(rest) = strip_archive_root(&entry_path) else {
+            continue;
+        };
+        let rest = if meta.path.is_empty() {
+            rest
+        } else if let Some(r) = rest.strip_prefix(&format!("{}/", meta.path)) {
+            r
+        } else {
+            continue;
+        };
+        if rest.is_empty() {
+            continue;
+        }
+        let dest = local_path.join(rest);
+        if entry.header().entry_type().is_dir() {
+            fs::create_dir_all(&dest)?;
+        } else {
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content)?;
+            file_shas.insert(rest.to_string(), git_blob_sha1(&content));
+            fs::write(&dest, &content)?;
+        }
+    }
+    Ok(file_shas)
+}
+
+fn download_dir(client: &Arc<Client>, meta: &Metadata, local_path: &Path, jobs: usize, old_shas: &HashMap<String, String>, max_retries: usize) -> Result<(HashMap<String, String>, Vec<DownloadError>), Box<dyn Error>> {
+    fs::create_dir_all(local_path)?;
+
+    let queue = Arc::new(WorkQueue::new());
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let ctx = Arc::new(SyncContext {
+        local_root: local_path.to_path_buf(),
+        old_shas: old_shas.clone(),
+        new_shas: Mutex::new(HashMap::new()),
+        max_retries,
+    });
+    // List the root directory synchronously, outside the worker pool, so a
+    // failure here (most importantly a 404 meaning the whole remote folder
+    // is gone) propagates to the caller as a real error instead of being
+    // swallowed into `errors` like a per-item failure further down the tree.
+    list_and_enqueue_dir(client, &queue, meta, local_path, max_retries)?;
+
+    let jobs = jobs.max(1);
+    let handles: Vec<_> = (0..jobs)
+        .map(|_| {
+            let client = Arc::clone(client);
+            let queue = Arc::clone(&queue);
+            let ctx = Arc::clone(&ctx);
+            let errors = Arc::clone(&errors);
+            thread::spawn(move || {
+                while let Some(item) = queue.pop() {
+                    if let Err(err) = process_work_item(&client, &queue, &ctx, item) {
+                        errors.lock().unwrap().push(err);
                     }
-                    let bytes = resp_file.bytes()?;
-                    fs::write(&local_file_path, &bytes)?;
                 }
-            }
-            "dir" => {
-                fs::create_dir_all(&local_file_path)?;
-                let sub_path = if meta.path.is_empty() {
-                    name.to_string()
-                } else {
-                    format!("{}/{}", meta.path, name)
-                };
-                let sub_meta = Metadata {
-                    owner: meta.owner.clone(),
-                    repo: meta.repo.clone(),
-                    reference: meta.reference.clone(),
-                    path: sub_path,
-                    url: meta.url.clone(),
-                };
-                download_dir(client, &sub_meta, &local_file_path)?;
-            }
-            _ => {}
+            })
+        })
+        .collect();
+    for handle in handles {
+        // Worker threads never panic under normal operation; if one did,
+        // the remaining workers still drain the queue on their own.
+        let _ = handle.join();
+    }
+
+    let errors = Arc::try_unwrap(errors).ok().map(|m| m.into_inner().unwrap()).unwrap_or_default();
+    let ctx = Arc::try_unwrap(ctx).ok().unwrap();
+    Ok((ctx.new_shas.into_inner().unwrap(), errors))
+}
+
+fn process_work_item(client: &Client, queue: &WorkQueue, ctx: &SyncContext, item: WorkItem) -> Result<(), DownloadError> {
+    match item {
+        WorkItem::Dir { meta, local_path } => {
+            let result = list_and_enqueue_dir(client, queue, &meta, &local_path, ctx.max_retries);
+            queue.complete_one();
+            let path = if meta.path.is_empty() { "/".to_string() } else { meta.path.clone() };
+            result.map_err(|e| DownloadError { path, message: e.to_string() })
         }
+        WorkItem::File { download_url, local_path, sha } => {
+            let result = sync_file(client, ctx, &download_url, &local_path, &sha);
+            queue.complete_one();
+            result.map_err(|e| DownloadError { path: local_path.display().to_string(), message: e.to_string() })
+        }
+    }
+}
+
+fn list_and_enqueue_dir(client: &Client, queue: &WorkQueue, meta: &Metadata, local_path: &Path, max_retries: usize) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(local_path)?;
+    let entries = host::list_dir(client, &meta.host, &meta.api_base, &meta.owner, &meta.repo, &meta.reference, &meta.path, max_retries)?;
+    for entry in entries {
+        let local_file_path = local_path.join(&entry.name);
+        if entry.is_dir {
+            let sub_meta = Metadata {
+                owner: meta.owner.clone(),
+                repo: meta.repo.clone(),
+                reference: meta.reference.clone(),
+                commit: meta.commit.clone(),
+                path: entry.path,
+                url: meta.url.clone(),
+                is_blob: false,
+                host: meta.host.clone(),
+                api_base: meta.api_base.clone(),
+                file_shas: HashMap::new(),
+            };
+            queue.push(WorkItem::Dir { meta: sub_meta, local_path: local_file_path });
+        } else {
+            queue.push(WorkItem::File { download_url: entry.download_url, local_path: local_file_path, sha: entry.sha });
+        }
+    }
+    Ok(())
+}
+
+/// Downloads `download_url` into `local_path` unless the file already
+/// there matches `remote_sha`, in which case it's left untouched. A sha
+/// recorded for this path during the previous run is trusted outright
+/// (the whole point of recording it is to make a no-op refresh not have
+/// to touch file content at all); otherwise the local content is hashed
+/// to check for a match before falling back to downloading.
+fn sync_file(client: &Client, ctx: &SyncContext, download_url: &str, local_path: &Path, remote_sha: &str) -> Result<(), Box<dyn Error>> {
+    let rel_path = relative_to_root(&ctx.local_root, local_path);
+    let up_to_date = match ctx.old_shas.get(&rel_path) {
+        Some(stored_sha) if stored_sha == remote_sha && local_path.is_file() => true,
+        _ => local_path.is_file() && git_blob_sha1(&fs::read(local_path)?) == remote_sha,
+    };
+    if !up_to_date {
+        download_file(client, download_url, local_path, ctx.max_retries)?;
     }
+    ctx.new_shas.lock().unwrap().insert(rel_path, remote_sha.to_string());
     Ok(())
 }
 
-#[derive(Deserialize)]
-struct Content {
-    name: String,
-    #[serde(rename = "type")]
-    r#type: String,
-    download_url: Option<String>,
+fn download_file(client: &Client, download_url: &str, local_path: &Path, max_retries: usize) -> Result<(), Box<dyn Error>> {
+    let resp = host::send_with_retry(client, download_url, max_retries)?;
+    if !resp.status().is_success() {
+        return Err(format!("Failed to download file {}: HTTP {}", download_url, resp.status()).into());
+    }
+    let bytes = resp.bytes()?;
+    fs::write(local_path, &bytes)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn git_blob_sha1_matches_git_hash_object() {
+        // `printf 'hello\n' | git hash-object --stdin`
+        assert_eq!(git_blob_sha1(b"hello\n"), "ce013625030ba8dba906f756967f9e9ca394464a");
+    }
+
+    #[test]
+    fn git_blob_sha1_empty_file() {
+        // `printf '' | git hash-object --stdin`
+        assert_eq!(git_blob_sha1(b""), "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391");
+    }
+
+    #[test]
+    fn diff_shas_counts_added_updated_unchanged() {
+        let mut old = HashMap::new();
+        old.insert("a.txt".to_string(), "sha_a".to_string());
+        old.insert("b.txt".to_string(), "sha_b".to_string());
+        let mut new = HashMap::new();
+        new.insert("a.txt".to_string(), "sha_a".to_string());
+        new.insert("b.txt".to_string(), "sha_b2".to_string());
+        new.insert("c.txt".to_string(), "sha_c".to_string());
+        assert_eq!(diff_shas(&old, &new), (1, 1, 1));
+    }
+
+    #[test]
+    fn resolve_mode_auto_prefers_archive_for_whole_repo_download() {
+        assert_eq!(resolve_mode(DownloadMode::Auto, "", false), DownloadMode::Archive);
+    }
+
+    #[test]
+    fn resolve_mode_auto_prefers_api_for_subfolder_download() {
+        assert_eq!(resolve_mode(DownloadMode::Auto, "src", false), DownloadMode::Api);
+    }
+
+    #[test]
+    fn resolve_mode_auto_prefers_api_for_whole_repo_refresh() {
+        // A refresh should favor the incremental Contents API path even
+        // for a whole-repo request, since Archive mode has no per-file
+        // sha to skip against.
+        assert_eq!(resolve_mode(DownloadMode::Auto, "", true), DownloadMode::Api);
+    }
+
+    #[test]
+    fn resolve_mode_explicit_mode_passes_through_regardless_of_refresh() {
+        assert_eq!(resolve_mode(DownloadMode::Archive, "", true), DownloadMode::Archive);
+        assert_eq!(resolve_mode(DownloadMode::Api, "", false), DownloadMode::Api);
+    }
+
+    #[test]
+    fn strip_archive_root_handles_tag_with_v_prefix_stripped() {
+        // `tar.gz/v2.0.0` extracts under `repo-2.0.0/`, not `repo-v2.0.0/`.
+        assert_eq!(strip_archive_root("repo-2.0.0/src/lib.rs"), Some("src/lib.rs"));
+    }
+
+    #[test]
+    fn strip_archive_root_handles_branch_with_slash_turned_to_dash() {
+        assert_eq!(strip_archive_root("repo-feature-foo/README.md"), Some("README.md"));
+    }
+
+    #[test]
+    fn strip_archive_root_top_level_entry_only() {
+        assert_eq!(strip_archive_root("repo-main"), None);
+    }
+
+    #[test]
+    fn parse_repo_link_tree_with_path() {
+        let client = Client::new();
+        let (host, _api_base, parsed) = parse_repo_link(&client, "https://github.com/owner/repo/tree/main/src/lib", 0).unwrap();
+        assert_eq!(host, HostKind::GitHub);
+        assert_eq!(parsed.owner, "owner");
+        assert_eq!(parsed.repo, "repo");
+        assert_eq!(parsed.reference, "main");
+        assert_eq!(parsed.path, "src/lib");
+        assert!(!parsed.is_blob);
+    }
+
+    #[test]
+    fn parse_repo_link_blob() {
+        let client = Client::new();
+        let (host, _api_base, parsed) = parse_repo_link(&client, "https://github.com/owner/repo/blob/main/README.md", 0).unwrap();
+        assert_eq!(host, HostKind::GitHub);
+        assert_eq!(parsed.path, "README.md");
+        assert!(parsed.is_blob);
+    }
+
+    #[test]
+    fn parse_repo_link_gitlab_tree_with_dash_marker() {
+        let client = Client::new();
+        let (host, _api_base, parsed) = parse_repo_link(&client, "https://gitlab.com/owner/repo/-/tree/main/src", 0).unwrap();
+        assert_eq!(host, HostKind::GitLab);
+        assert_eq!(parsed.owner, "owner");
+        assert_eq!(parsed.repo, "repo");
+        assert_eq!(parsed.path, "src");
+    }
+
+    #[test]
+    fn parse_repo_link_sourcehut_blob_strips_tilde_owner() {
+        let client = Client::new();
+        let (host, _api_base, parsed) = parse_repo_link(&client, "https://git.sr.ht/~owner/repo/blob/main/file.txt", 0).unwrap();
+        assert_eq!(host, HostKind::SourceHut);
+        assert_eq!(parsed.owner, "owner");
+        assert_eq!(parsed.path, "file.txt");
+        assert!(parsed.is_blob);
+    }
+
+    #[test]
+    fn parse_repo_link_bare_commit_sha_reference() {
+        let client = Client::new();
+        let sha = "a".repeat(40);
+        let link = format!("https://github.com/owner/repo/tree/{}/src", sha);
+        let (_host, _api_base, parsed) = parse_repo_link(&client, &link, 0).unwrap();
+        assert_eq!(parsed.reference, sha);
+    }
 }
 
 fn find_metadata_files(dir: &Path, result: &mut Vec<PathBuf>) -> std::io::Result<()> {